@@ -0,0 +1,124 @@
+// IPアドレスからおおよその現在地を推定し、最寄りの気象庁予報区コードに
+// マッピングするための処理。
+//
+// APIキー不要のIPジオロケーションサービスで緯度経度を取得し、あらかじめ
+// 用意した代表地点テーブルとの大圏距離（ハーバサイン公式）が最小になる
+// 地点を選ぶことで、予報区コードをハードコードせずに済むようにする。
+
+// 座標が取得できなかった場合にフォールバックする予報区コード（東京）
+pub const DEFAULT_AREA_CODE: &str = "130000";
+
+// 気象庁予報区コードとその代表地点の緯度経度
+struct JmaArea {
+    code: &'static str,
+    lat: f64,
+    lon: f64,
+}
+
+const JMA_AREAS: &[JmaArea] = &[
+    JmaArea { code: "011000", lat: 43.0642, lon: 141.3469 }, // 札幌
+    JmaArea { code: "040000", lat: 38.2682, lon: 140.8694 }, // 仙台
+    JmaArea { code: "130000", lat: 35.6895, lon: 139.6917 }, // 東京
+    JmaArea { code: "170000", lat: 37.9026, lon: 139.0232 }, // 新潟
+    JmaArea { code: "200000", lat: 36.6513, lon: 138.1810 }, // 長野
+    JmaArea { code: "230000", lat: 35.1802, lon: 136.9066 }, // 名古屋
+    JmaArea { code: "270000", lat: 34.6937, lon: 135.5023 }, // 大阪
+    JmaArea { code: "340000", lat: 34.3966, lon: 132.4596 }, // 広島
+    JmaArea { code: "380000", lat: 33.8416, lon: 132.7657 }, // 松山
+    JmaArea { code: "400000", lat: 33.5904, lon: 130.4017 }, // 福岡
+    JmaArea { code: "430000", lat: 32.7898, lon: 130.7417 }, // 熊本
+    JmaArea { code: "471000", lat: 26.2124, lon: 127.6809 }, // 那覇
+];
+
+// IPジオロケーションAPIのレスポンス（ipapi.co）
+#[derive(serde::Deserialize)]
+struct IpLocation {
+    latitude: f64,
+    longitude: f64,
+}
+
+// ハーバサイン公式による2地点間の大圏距離（km）
+fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+
+    let (lat1, lon1, lat2, lon2) = (
+        lat1.to_radians(),
+        lon1.to_radians(),
+        lat2.to_radians(),
+        lon2.to_radians(),
+    );
+
+    let dlat = lat2 - lat1;
+    let dlon = lon2 - lon1;
+
+    let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    let c = 2.0 * a.sqrt().asin();
+
+    EARTH_RADIUS_KM * c
+}
+
+// 緯度経度に最も近い気象庁予報区コードを返す
+fn nearest_area_code(lat: f64, lon: f64) -> &'static str {
+    JMA_AREAS
+        .iter()
+        .min_by(|a, b| {
+            haversine_km(lat, lon, a.lat, a.lon)
+                .partial_cmp(&haversine_km(lat, lon, b.lat, b.lon))
+                .unwrap()
+        })
+        .map(|area| area.code)
+        .unwrap_or(DEFAULT_AREA_CODE)
+}
+
+// IPアドレスから緯度経度を取得する
+async fn locate_by_ip() -> Result<(f64, f64), String> {
+    let response = reqwest::get("https://ipapi.co/json")
+        .await
+        .map_err(|e| format!("ネットワークエラー: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("位置情報APIエラー: {}", response.status()));
+    }
+
+    let location = response
+        .json::<IpLocation>()
+        .await
+        .map_err(|e| format!("JSONパースエラー: {}", e))?;
+
+    Ok((location.latitude, location.longitude))
+}
+
+// IPアドレスから現在地を推定し、最も近い気象庁予報区コードを返す
+//
+// 位置情報の取得に失敗した場合は`DEFAULT_AREA_CODE`にフォールバックする
+pub async fn resolve_area_code() -> String {
+    match locate_by_ip().await {
+        Ok((lat, lon)) => nearest_area_code(lat, lon).to_string(),
+        Err(_) => DEFAULT_AREA_CODE.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn haversine_km_is_zero_for_same_point() {
+        assert!(haversine_km(35.6895, 139.6917, 35.6895, 139.6917) < 1e-6);
+    }
+
+    #[test]
+    fn haversine_km_matches_known_tokyo_fukuoka_distance() {
+        // 東京-福岡間はおよそ880km
+        let distance = haversine_km(35.6895, 139.6917, 33.5904, 130.4017);
+        assert!((800.0..950.0).contains(&distance), "distance was {distance}");
+    }
+
+    #[test]
+    fn nearest_area_code_picks_the_closest_representative_point() {
+        // 東京の代表地点そのものを渡せば東京が選ばれる
+        assert_eq!(nearest_area_code(35.6895, 139.6917), "130000");
+        // 那覇の代表地点に近い座標を渡せば那覇が選ばれる
+        assert_eq!(nearest_area_code(26.2, 127.7), "471000");
+    }
+}
@@ -6,8 +6,13 @@ use tauri_plugin_notification::NotificationExt;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
-use chrono::Timelike;  
-use serde::{Deserialize, Serialize};
+use chrono::Timelike;
+
+mod location;
+mod weather;
+use weather::{
+    Jma, OpenWeatherMap, WeatherInfo, WeatherProvider, WeatherState, DEFAULT_FORECAST_HOURS,
+};
 
 // 通知の状態を管理する構造体
 #[derive(Debug, Default)]
@@ -15,14 +20,29 @@ struct NotificationState {
     enabled: bool,
 }
 
-// 天気情報を格納する構造体
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
-struct WeatherInfo {
-    description: String,
-    temperature: f64,
-    weather_code: String,
-    humidity: i32,
-    icon: String,
+// 通知の文面テンプレートを管理する構造体
+//
+// `$time`/`$weather`/`$temp`/`$icon`のプレースホルダーを埋め込める
+#[derive(Debug, Clone)]
+struct NotificationConfig {
+    template: String,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            template: "$time".to_string(),
+        }
+    }
+}
+
+// テンプレート中のプレースホルダーを実際の値に置き換える
+fn render_notification_template(template: &str, time: &str, weather: &WeatherInfo) -> String {
+    template
+        .replace("$time", time)
+        .replace("$weather", &weather.description)
+        .replace("$temp", &format!("{:.0}°C", weather.temperature))
+        .replace("$icon", &weather.icon)
 }
 
 // 現在時刻を取得するコマンド
@@ -68,185 +88,105 @@ fn get_notification_state(
     Ok(state.enabled)
 }
 
-// 気象庁APIから天気情報を取得する共通関数
-async fn fetch_weather_info(url: &str) -> Result<WeatherInfo, String> {
-    match reqwest::get(url).await {
-        Ok(response) => {
-            if response.status().is_success() {
-                match response.json::<serde_json::Value>().await {
-                    Ok(data) => {
-                        let weather_data = &data[0]["timeSeries"][0]["areas"][0];
-                        let weather_code = weather_data["weatherCodes"][0]
-                            .as_str()
-                            .unwrap_or("")
-                            .to_string();
-                        let weather_description = weather_data["weathers"][0]
-                            .as_str()
-                            .unwrap_or("不明")
-                            .to_string();
-                        let temp_data = &data[0]["timeSeries"][2]["areas"][0];
-                        let temperature = temp_data["temps"][0]
-                            .as_str()
-                            .unwrap_or("0")
-                            .parse::<f64>()
-                            .unwrap_or(0.0);
-                        let humidity = 50; // デフォルト値
-                        let icon = match weather_code.as_str() {
-                            "100" | "123" | "124" | "130" | "131" => "01d",
-                            "101" | "132" | "140" | "160" | "170" => "02d",
-                            "102" | "104" | "115" | "116" | "141" | "142" => "03d",
-                            "103" | "106" | "107" | "108" | "128" | "143" | "150" => "04d",
-                            "110" | "111" | "112" | "113" | "114" | "118" | "119" | "125"
-                            | "126" | "127" | "153" | "154" | "155" | "181" => "09d",
-                            "117" | "181" => "11d",
-                            "120" | "121" | "122" | "156" | "157" | "160" => "13d",
-                            _ => "50d",
-                        };
-
-                        Ok(WeatherInfo {
-                            description: weather_description,
-                            temperature,
-                            weather_code,
-                            humidity,
-                            icon: icon.to_string(),
-                        })
-                    }
-                    Err(e) => Err(format!("JSONパースエラー: {}", e)),
-                }
-            } else {
-                Err(format!("API呼び出しエラー: {}", response.status()))
-            }
-        }
-        Err(e) => Err(format!("ネットワークエラー: {}", e)),
-    }
+// 通知の文面テンプレートを設定するコマンド
+//
+// `$time`/`$weather`/`$temp`/`$icon`のプレースホルダーが使える
+#[tauri::command]
+fn set_notification_format(
+    config_state: tauri::State<'_, Arc<Mutex<NotificationConfig>>>,
+    template: String,
+) -> Result<(), String> {
+    let mut config = config_state.lock().map_err(|e| e.to_string())?;
+    config.template = template;
+    Ok(())
 }
 
-// 東京の天気を取得するコマンド
+// 指定したプロバイダ・地点の天気を取得するコマンド
+//
+// `provider`は"jma"（気象庁、locationは予報区コード）または
+// "openweathermap"（locationは都市名）を受け付ける。OpenWeatherMap用の
+// APIキーが未設定の場合はデモデータにフォールバックする。`forecast_hours`を
+// 省略した場合は`DEFAULT_FORECAST_HOURS`件まで予報を返す。`provider`/
+// `location`は取得に成功した場合のみ`WeatherState`に書き戻す。失敗した
+// リクエストで通知スレッドの参照先を壊れたままにしないため
 #[tauri::command]
-async fn get_tokyo_weather() -> Result<WeatherInfo, String> {
-    fetch_weather_info("https://www.jma.go.jp/bosai/forecast/data/forecast/130000.json").await
+async fn get_weather(
+    weather_state: tauri::State<'_, Arc<Mutex<WeatherState>>>,
+    provider: String,
+    location: String,
+    forecast_hours: Option<usize>,
+) -> Result<WeatherInfo, String> {
+    let (api_key, options) = {
+        let state = weather_state.lock().map_err(|e| e.to_string())?;
+        (
+            state.api_key.clone(),
+            state.options(forecast_hours.unwrap_or(DEFAULT_FORECAST_HOURS)),
+        )
+    };
+
+    let result = match provider.as_str() {
+        "jma" => Jma.fetch(&location, &options).await,
+        "openweathermap" => OpenWeatherMap { api_key }.fetch(&location, &options).await,
+        other => Err(format!("不明な天気プロバイダです: {}", other)),
+    };
+
+    if result.is_ok() {
+        let mut state = weather_state.lock().map_err(|e| e.to_string())?;
+        state.provider = provider;
+        state.location = location;
+    }
+
+    result
 }
 
-// 福岡の天気を取得するコマンド
+// IPアドレスから現在地を推定し、最寄りの気象庁予報区の天気を取得するコマンド
+//
+// 位置情報の取得に失敗した場合は`location::DEFAULT_AREA_CODE`にフォールバック
+// するので、呼び出し側はネットワーク事情を気にせず呼べる。解決した地点は
+// 取得に成功した場合のみ`WeatherState`にも書き戻すので、以降の通知スレッドや
+// `get_weather`もこの地点を引き継ぐ
 #[tauri::command]
-async fn get_fukuoka_weather() -> Result<WeatherInfo, String> {
-    fetch_weather_info("https://www.jma.go.jp/bosai/forecast/data/forecast/400000.json").await
-}
+async fn get_weather_autolocated(
+    weather_state: tauri::State<'_, Arc<Mutex<WeatherState>>>,
+    forecast_hours: Option<usize>,
+) -> Result<WeatherInfo, String> {
+    let area_code = location::resolve_area_code().await;
+    let options = {
+        let state = weather_state.lock().map_err(|e| e.to_string())?;
+        state.options(forecast_hours.unwrap_or(DEFAULT_FORECAST_HOURS))
+    };
 
-// 気象庁の天気コードを日本語の説明に変換するヘルパー（必要に応じて）
-fn get_weather_description_from_code(code: &str) -> &str {
-    match code {
-        "100" => "晴れ",
-        "101" => "晴れ時々曇り",
-        "102" => "晴れ一時雨",
-        "103" => "晴れ時々雨",
-        "104" => "晴れ一時雪",
-        "105" => "晴れ時々雪",
-        "106" => "晴れ一時雨か雪",
-        "107" => "晴れ時々雨か雪",
-        "108" => "晴れ一時雨か雷雨",
-        "110" => "曇り",
-        "111" => "曇り時々晴れ",
-        "112" => "曇り一時雨",
-        "113" => "曇り時々雨",
-        "114" => "曇り一時雪",
-        "115" => "曇り時々雪",
-        "116" => "曇り一時雨か雪",
-        "117" => "曇り時々雨か雪",
-        "118" => "曇り一時雨か雷雨",
-        "119" => "曇り時々雨か雷雨",
-        "120" => "雨",
-        "121" => "雨時々晴れ",
-        "122" => "雨時々曇り",
-        "123" => "雨一時雪",
-        "124" => "雨時々雪",
-        "125" => "雨一時雪か雷雨",
-        "126" => "雨時々雪か雷雨",
-        "127" => "雨か雷雨",
-        "130" => "雪",
-        "131" => "雪時々晴れ",
-        "132" => "雪時々曇り",
-        "140" => "晴れ",
-        "141" => "晴れ時々曇り",
-        "142" => "晴れ一時雨",
-        "150" => "曇り",
-        "160" => "雨",
-        "170" => "雪",
-        "181" => "雷",
-        _ => "不明",
+    let result = Jma.fetch(&area_code, &options).await;
+
+    if result.is_ok() {
+        let mut state = weather_state.lock().map_err(|e| e.to_string())?;
+        state.provider = "jma".to_string();
+        state.location = area_code;
     }
+
+    result
 }
 
-// APIキーがない場合のデモ用の天気データを返す関数
+// 天気取得の単位（"metric"/"imperial"）を設定するコマンド
 #[tauri::command]
-async fn get_tokyo_weather_demo() -> Result<WeatherInfo, String> {
-    // デモ用のモックデータ
-    let mock_weathers = vec![
-        WeatherInfo {
-            description: "晴れ".to_string(),
-            temperature: 22.5,
-            weather_code: "100".to_string(),
-            humidity: 45,
-            icon: "01d".to_string(),
-        },
-        WeatherInfo {
-            description: "曇り".to_string(),
-            temperature: 18.3,
-            weather_code: "110".to_string(),
-            humidity: 65,
-            icon: "03d".to_string(),
-        },
-        WeatherInfo {
-            description: "小雨".to_string(),
-            temperature: 15.8,
-            weather_code: "120".to_string(),
-            humidity: 78,
-            icon: "10d".to_string(),
-        },
-    ];
-
-    // 現在の時刻から適当なデータを選択
-    use chrono::Local;
-    let now = Local::now();
-    let index = (now.minute() % 3) as usize;
-    
-    Ok(mock_weathers[index].clone())
+fn set_weather_units(
+    weather_state: tauri::State<'_, Arc<Mutex<WeatherState>>>,
+    units: String,
+) -> Result<(), String> {
+    let mut state = weather_state.lock().map_err(|e| e.to_string())?;
+    state.units = units;
+    Ok(())
 }
 
-// APIキーがない場合のデモ用の福岡天気データを返す関数
+// 天気取得の言語（OpenWeatherMapのlangクエリパラメータ）を設定するコマンド
 #[tauri::command]
-async fn get_fukuoka_weather_demo() -> Result<WeatherInfo, String> {
-    // デモ用のモックデータ
-    let mock_weathers = vec![
-        WeatherInfo {
-            description: "福岡 - 晴れ".to_string(),
-            temperature: 23.5,
-            weather_code: "100".to_string(),
-            humidity: 42,
-            icon: "01d".to_string(),
-        },
-        WeatherInfo {
-            description: "福岡 - 曇り".to_string(),
-            temperature: 19.8,
-            weather_code: "110".to_string(),
-            humidity: 60,
-            icon: "03d".to_string(),
-        },
-        WeatherInfo {
-            description: "福岡 - 小雨".to_string(),
-            temperature: 17.2,
-            weather_code: "120".to_string(),
-            humidity: 75,
-            icon: "10d".to_string(),
-        },
-    ];
-
-    // 現在の時刻から適当なデータを選択
-    use chrono::Local;
-    let now = Local::now();
-    let index = (now.minute() % 3) as usize;
-    
-    Ok(mock_weathers[index].clone())
+fn set_weather_lang(
+    weather_state: tauri::State<'_, Arc<Mutex<WeatherState>>>,
+    lang: String,
+) -> Result<(), String> {
+    let mut state = weather_state.lock().map_err(|e| e.to_string())?;
+    state.lang = lang;
+    Ok(())
 }
 
 fn main() {
@@ -254,9 +194,21 @@ fn main() {
     let notification_state = Arc::new(Mutex::new(NotificationState { enabled: false }));
     let notification_state_clone = notification_state.clone();
 
+    // 通知の文面テンプレートを管理するための共有状態
+    let notification_config = Arc::new(Mutex::new(NotificationConfig::default()));
+    let notification_config_clone = notification_config.clone();
+
+    // 天気プロバイダの選択とAPIキーを管理するための共有状態
+    let weather_state = Arc::new(Mutex::new(WeatherState::new()));
+    let weather_state_clone = weather_state.clone();
+
     tauri::Builder::default()
         // 通知状態を管理する
         .manage(notification_state)
+        // 通知の文面テンプレートを管理する
+        .manage(notification_config)
+        // 天気プロバイダの状態を管理する
+        .manage(weather_state)
         // プラグインを登録
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_shell::init())
@@ -266,14 +218,15 @@ fn main() {
             send_notification,
             toggle_notification,
             get_notification_state,
-            get_tokyo_weather,
-            get_tokyo_weather_demo,
-            get_fukuoka_weather,
-            get_fukuoka_weather_demo
+            set_notification_format,
+            get_weather,
+            get_weather_autolocated,
+            set_weather_units,
+            set_weather_lang
         ])
         .setup(|app| {
             let app_handle = app.handle().clone();
-            
+
             // 通知を担当するバックグラウンドスレッド
             thread::spawn(move || {
                 loop {
@@ -308,17 +261,45 @@ fn main() {
                     
                     // 通知が有効な場合は通知を送信
                     if is_enabled {
-                        // 現在時刻を再取得（待機後）                        
+                        // 現在時刻を再取得（待機後）
                         let now = chrono::Local::now();
                         // 分が5の倍数かどうか確認（念のため）
                         if now.minute() % 5 == 0 {
                             let time_str = now.format("%Y-%m-%d %H:%M:%S").to_string();
-                            
+
+                            let template = {
+                                let config = notification_config_clone.lock().unwrap();
+                                config.template.clone()
+                            };
+
+                            let (provider, location, api_key, options) = {
+                                let state = weather_state_clone.lock().unwrap();
+                                (
+                                    state.provider.clone(),
+                                    state.location.clone(),
+                                    state.api_key.clone(),
+                                    state.options(0),
+                                )
+                            };
+
+                            // 通知文面に埋め込む天気情報を取得（予報は不要なので0件）
+                            let weather = tauri::async_runtime::block_on(async {
+                                match provider.as_str() {
+                                    "openweathermap" => {
+                                        OpenWeatherMap { api_key }.fetch(&location, &options).await
+                                    }
+                                    _ => Jma.fetch(&location, &options).await,
+                                }
+                            })
+                            .unwrap_or_default();
+
+                            let body = render_notification_template(&template, &time_str, &weather);
+
                             // 通知を送信
                             let _ = app_handle.notification()
                                 .builder()
                                 .title("現在時刻（5分刻み）")
-                                .body(format!("現在の時刻は {} です", time_str))
+                                .body(body)
                                 .show();
                         }
                     }
@@ -333,3 +314,35 @@ fn main() {
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_weather() -> WeatherInfo {
+        WeatherInfo {
+            description: "晴れ".to_string(),
+            temperature: 23.4,
+            icon: "01d".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn render_notification_template_substitutes_all_placeholders() {
+        let weather = sample_weather();
+        let body = render_notification_template(
+            "$time $weather $temp $icon",
+            "2024-01-01 09:00:00",
+            &weather,
+        );
+        assert_eq!(body, "2024-01-01 09:00:00 晴れ 23°C 01d");
+    }
+
+    #[test]
+    fn render_notification_template_leaves_unknown_placeholders_untouched() {
+        let weather = sample_weather();
+        let body = render_notification_template("$time $unknown", "09:00", &weather);
+        assert_eq!(body, "09:00 $unknown");
+    }
+}
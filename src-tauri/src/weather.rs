@@ -0,0 +1,535 @@
+// 天気情報取得まわりのプロバイダ抽象化
+//
+// JMA（気象庁）とOpenWeatherMapの両方から同じ`WeatherInfo`形状でデータを
+// 取得できるようにし、呼び出し側（main.rsのコマンド）がバックエンドの違いを
+// 意識しなくて済むようにする。
+
+use serde::{Deserialize, Serialize};
+
+// 既定で返す先の時間数の天気予報エントリ数
+pub const DEFAULT_FORECAST_HOURS: usize = 8;
+
+// 天気情報を格納する構造体
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct WeatherInfo {
+    pub description: String,
+    pub temperature: f64,
+    pub weather_code: String,
+    pub humidity: i32,
+    pub icon: String,
+    // これから先の時間帯の予報（時刻の昇順）
+    pub forecast: Vec<ForecastEntry>,
+    // 直近の予報と比べた気温の傾向（"↑"/"↓"/"→"）
+    pub trend: String,
+}
+
+// 1時間（または1コマ）分の予報エントリ
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ForecastEntry {
+    pub time: String,
+    pub temperature: f64,
+    pub weather_code: String,
+    pub icon: String,
+}
+
+// 天気プロバイダの選択・APIキー・単位/言語設定を管理する状態
+//
+// `location`は直近に`get_weather`/`get_weather_autolocated`で取得に成功した
+// 地点で、バックグラウンドの
+// 通知スレッドがどの地点の天気を読むかはここを参照して決める
+#[derive(Debug)]
+pub struct WeatherState {
+    pub provider: String,
+    pub location: String,
+    pub api_key: Option<String>,
+    pub units: String,
+    pub lang: String,
+}
+
+impl WeatherState {
+    // デフォルトのプロバイダはJMA（地点は`DEFAULT_AREA_CODE`）。APIキーは
+    // OPENWEATHERMAP_API_KEY環境変数があればそれを読み込んでおく
+    pub fn new() -> Self {
+        Self {
+            provider: "jma".to_string(),
+            location: crate::location::DEFAULT_AREA_CODE.to_string(),
+            api_key: std::env::var("OPENWEATHERMAP_API_KEY").ok(),
+            units: "metric".to_string(),
+            lang: "ja".to_string(),
+        }
+    }
+
+    pub fn options(&self, forecast_hours: usize) -> WeatherOptions {
+        WeatherOptions {
+            forecast_hours,
+            units: self.units.clone(),
+            lang: self.lang.clone(),
+        }
+    }
+}
+
+impl Default for WeatherState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 天気取得時に渡す単位・言語などの付加オプション
+#[derive(Debug, Clone)]
+pub struct WeatherOptions {
+    pub forecast_hours: usize,
+    // "metric"（摂氏）または"imperial"（華氏）
+    pub units: String,
+    // OpenWeatherMapのlangクエリパラメータ。JMAでは説明文の言語切り替えには
+    // 未対応（気象庁のデータが日本語のみのため）
+    pub lang: String,
+}
+
+impl Default for WeatherOptions {
+    fn default() -> Self {
+        Self {
+            forecast_hours: DEFAULT_FORECAST_HOURS,
+            units: "metric".to_string(),
+            lang: "ja".to_string(),
+        }
+    }
+}
+
+// 摂氏を華氏に変換する
+fn celsius_to_fahrenheit(celsius: f64) -> f64 {
+    celsius * 9.0 / 5.0 + 32.0
+}
+
+// 天気情報を取得するプロバイダの共通インターフェース
+pub trait WeatherProvider {
+    async fn fetch(&self, location: &str, options: &WeatherOptions) -> Result<WeatherInfo, String>;
+}
+
+// 気象庁（JMA）から天気情報を取得するプロバイダ
+//
+// `location`には気象庁の予報区コード（東京=130000、福岡=400000など）を渡す
+pub struct Jma;
+
+impl WeatherProvider for Jma {
+    async fn fetch(&self, location: &str, options: &WeatherOptions) -> Result<WeatherInfo, String> {
+        let url = format!("https://www.jma.go.jp/bosai/forecast/data/forecast/{}.json", location);
+        fetch_jma_weather(&url, options).await
+    }
+}
+
+// OpenWeatherMapから天気情報を取得するプロバイダ
+//
+// `location`には都市名（例: "Tokyo"）を渡す。APIキーが無い場合はデモデータに
+// フォールバックする
+pub struct OpenWeatherMap {
+    pub api_key: Option<String>,
+}
+
+impl WeatherProvider for OpenWeatherMap {
+    async fn fetch(&self, location: &str, options: &WeatherOptions) -> Result<WeatherInfo, String> {
+        let Some(api_key) = &self.api_key else {
+            return Ok(get_weather_demo(location, options));
+        };
+
+        let url = format!(
+            "https://api.openweathermap.org/data/2.5/weather?q={}&units={}&lang={}&appid={}",
+            location, options.units, options.lang, api_key
+        );
+
+        match reqwest::get(&url).await {
+            Ok(response) => {
+                if response.status().is_success() {
+                    match response.json::<serde_json::Value>().await {
+                        Ok(data) => Ok(parse_openweathermap_response(&data)),
+                        Err(e) => Err(format!("JSONパースエラー: {}", e)),
+                    }
+                } else {
+                    Err(format!("API呼び出しエラー: {}", response.status()))
+                }
+            }
+            Err(e) => Err(format!("ネットワークエラー: {}", e)),
+        }
+    }
+}
+
+fn parse_openweathermap_response(data: &serde_json::Value) -> WeatherInfo {
+    let description = data["weather"][0]["description"]
+        .as_str()
+        .unwrap_or("不明")
+        .to_string();
+    let weather_code = data["weather"][0]["id"]
+        .as_i64()
+        .map(|id| id.to_string())
+        .unwrap_or_default();
+    let temperature = data["main"]["temp"].as_f64().unwrap_or(0.0);
+    let humidity = data["main"]["humidity"].as_i64().unwrap_or(50) as i32;
+    // OpenWeatherMapのiconは自前のsunrise/sunsetを踏まえて昼夜を判定済みなので
+    // そのまま使う（例: "01d"/"01n"）
+    let icon = data["weather"][0]["icon"]
+        .as_str()
+        .unwrap_or("50d")
+        .to_string();
+
+    WeatherInfo {
+        description,
+        temperature,
+        weather_code,
+        humidity,
+        icon,
+        forecast: Vec::new(),
+        trend: "→".to_string(),
+    }
+}
+
+// 天気コードに対応するアイコンの基本コード（昼夜の接尾辞を除いたもの）
+//
+// "160"（雨）は"13"、"181"（雷）は"11"にのみ属する。matchは先に書かれた腕が
+// 勝つので、他の降水系の腕と重複させると後ろの腕のアイコンが選ばれなくなるため
+// 重複させない
+fn icon_code_for(weather_code: &str) -> &'static str {
+    match weather_code {
+        "100" | "123" | "124" | "130" | "131" => "01",
+        "101" | "132" | "140" | "170" => "02",
+        "102" | "104" | "115" | "116" | "141" | "142" => "03",
+        "103" | "106" | "107" | "108" | "128" | "143" | "150" => "04",
+        "110" | "111" | "112" | "113" | "114" | "118" | "119" | "125" | "126" | "127" | "153"
+        | "154" | "155" => "09",
+        "117" | "181" => "11",
+        "120" | "121" | "122" | "156" | "157" | "160" => "13",
+        _ => "50",
+    }
+}
+
+// 天気コードと時刻から昼(`d`)/夜(`n`)を判定してアイコン名を決める
+//
+// `sun_hours`に`Some((sunrise_hour, sunset_hour))`を渡すとその時刻を日の出・
+// 日の入りとして使う（OpenWeatherMapはこれを返す）。渡さない場合は6時〜18時を
+// 昼とみなす簡易判定にフォールバックする
+fn resolve_icon(weather_code: &str, local_hour: u32, sun_hours: Option<(u32, u32)>) -> String {
+    let (sunrise, sunset) = sun_hours.unwrap_or((6, 18));
+    let suffix = if local_hour >= sunrise && local_hour < sunset {
+        "d"
+    } else {
+        "n"
+    };
+
+    format!("{}{}", icon_code_for(weather_code), suffix)
+}
+
+// JMAの`timeDefines`の時刻文字列（RFC3339）から現地時刻の「時」を取り出す
+//
+// パースに失敗した場合は現在時刻の時を使う
+fn hour_from_jma_time(time: &str) -> u32 {
+    use chrono::Timelike;
+
+    chrono::DateTime::parse_from_rfc3339(time)
+        .map(|dt| dt.hour())
+        .unwrap_or_else(|_| chrono::Local::now().hour())
+}
+
+// `timeSeries[2]`（気温）の`timeDefines`の中から、`target_time`に最も近い
+// 時刻のインデックスを探す
+//
+// 気象庁APIの`timeSeries`は系列ごとに独立した`timeDefines`を持ち（天気は
+// 1日3コマ、気温は1日2コマなど）、件数も粒度も異なるため、同じインデックス
+// でズレなく揃っているとは限らない。時刻同士を比較して一番近いものを選ぶ
+fn nearest_temp_index(target_time: &str, temp_times: &[serde_json::Value]) -> Option<usize> {
+    let target = chrono::DateTime::parse_from_rfc3339(target_time).ok()?;
+
+    temp_times
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| {
+            let parsed = chrono::DateTime::parse_from_rfc3339(t.as_str()?).ok()?;
+            Some((i, (parsed - target).num_seconds().abs()))
+        })
+        .min_by_key(|(_, diff)| *diff)
+        .map(|(i, _)| i)
+}
+
+// 現在の気温と直近の予報エントリを比べて気温の傾向を求める
+fn compute_trend(current_temperature: f64, forecast: &[ForecastEntry]) -> String {
+    match forecast.first() {
+        Some(next) if next.temperature - current_temperature > 1.0 => "↑".to_string(),
+        Some(next) if current_temperature - next.temperature > 1.0 => "↓".to_string(),
+        _ => "→".to_string(),
+    }
+}
+
+// 気象庁APIから天気情報を取得する共通関数
+pub async fn fetch_jma_weather(url: &str, options: &WeatherOptions) -> Result<WeatherInfo, String> {
+    match reqwest::get(url).await {
+        Ok(response) => {
+            if response.status().is_success() {
+                match response.json::<serde_json::Value>().await {
+                    Ok(data) => Ok(parse_jma_weather(&data, options)),
+                    Err(e) => Err(format!("JSONパースエラー: {}", e)),
+                }
+            } else {
+                Err(format!("API呼び出しエラー: {}", response.status()))
+            }
+        }
+        Err(e) => Err(format!("ネットワークエラー: {}", e)),
+    }
+}
+
+fn parse_jma_weather(data: &serde_json::Value, options: &WeatherOptions) -> WeatherInfo {
+    let weather_area = &data[0]["timeSeries"][0]["areas"][0];
+    let weather_times = data[0]["timeSeries"][0]["timeDefines"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let weather_codes = weather_area["weatherCodes"].as_array().cloned().unwrap_or_default();
+
+    let temp_area = &data[0]["timeSeries"][2]["areas"][0];
+    let temp_times = data[0]["timeSeries"][2]["timeDefines"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let temps = temp_area["temps"].as_array().cloned().unwrap_or_default();
+
+    let weather_code = weather_codes
+        .first()
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    // コードから説明文を求め、テーブルに無いコードのみ気象庁の生テキストに
+    // フォールバックする（JMAは日本語しか返さないためlangでの切り替えはしない）
+    let weather_description = match get_weather_description_from_code(&weather_code) {
+        "不明" => weather_area["weathers"][0].as_str().unwrap_or("不明").to_string(),
+        description => description.to_string(),
+    };
+    let current_time = weather_times.first().and_then(|v| v.as_str()).unwrap_or("");
+    let current_temp_index = nearest_temp_index(current_time, &temp_times).unwrap_or(0);
+    let mut temperature = temps
+        .get(current_temp_index)
+        .and_then(|v| v.as_str())
+        .and_then(|s| s.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    let humidity = 50; // デフォルト値
+    let icon = resolve_icon(&weather_code, hour_from_jma_time(current_time), None);
+
+    // 現時点のエントリ（インデックス0）の先の時間帯を予報として切り出す
+    let mut forecast: Vec<ForecastEntry> = weather_times
+        .iter()
+        .zip(weather_codes.iter())
+        .skip(1)
+        .take(options.forecast_hours)
+        .map(|(time, code)| {
+            let code_str = code.as_str().unwrap_or("").to_string();
+            let time_str = time.as_str().unwrap_or("").to_string();
+            // 気温系列は天気系列と`timeDefines`の粒度が異なるため、インデックスを
+            // 揃えるのではなく時刻が一番近いコマの気温を拾う
+            let entry_temperature = nearest_temp_index(&time_str, &temp_times)
+                .and_then(|i| temps.get(i))
+                .and_then(|v| v.as_str())
+                .and_then(|s| s.parse::<f64>().ok())
+                .unwrap_or(temperature);
+            let entry_icon = resolve_icon(&code_str, hour_from_jma_time(&time_str), None);
+
+            ForecastEntry {
+                time: time_str,
+                temperature: entry_temperature,
+                icon: entry_icon,
+                weather_code: code_str,
+            }
+        })
+        .collect();
+
+    let trend = compute_trend(temperature, &forecast);
+
+    if options.units == "imperial" {
+        temperature = celsius_to_fahrenheit(temperature);
+        for entry in &mut forecast {
+            entry.temperature = celsius_to_fahrenheit(entry.temperature);
+        }
+    }
+
+    WeatherInfo {
+        description: weather_description,
+        temperature,
+        weather_code,
+        humidity,
+        icon,
+        forecast,
+        trend,
+    }
+}
+
+// 気象庁の天気コードを日本語の説明に変換するヘルパー（必要に応じて）
+pub fn get_weather_description_from_code(code: &str) -> &str {
+    match code {
+        "100" => "晴れ",
+        "101" => "晴れ時々曇り",
+        "102" => "晴れ一時雨",
+        "103" => "晴れ時々雨",
+        "104" => "晴れ一時雪",
+        "105" => "晴れ時々雪",
+        "106" => "晴れ一時雨か雪",
+        "107" => "晴れ時々雨か雪",
+        "108" => "晴れ一時雨か雷雨",
+        "110" => "曇り",
+        "111" => "曇り時々晴れ",
+        "112" => "曇り一時雨",
+        "113" => "曇り時々雨",
+        "114" => "曇り一時雪",
+        "115" => "曇り時々雪",
+        "116" => "曇り一時雨か雪",
+        "117" => "曇り時々雨か雪",
+        "118" => "曇り一時雨か雷雨",
+        "119" => "曇り時々雨か雷雨",
+        "120" => "雨",
+        "121" => "雨時々晴れ",
+        "122" => "雨時々曇り",
+        "123" => "雨一時雪",
+        "124" => "雨時々雪",
+        "125" => "雨一時雪か雷雨",
+        "126" => "雨時々雪か雷雨",
+        "127" => "雨か雷雨",
+        "130" => "雪",
+        "131" => "雪時々晴れ",
+        "132" => "雪時々曇り",
+        "140" => "晴れ",
+        "141" => "晴れ時々曇り",
+        "142" => "晴れ一時雨",
+        "150" => "曇り",
+        "160" => "雨",
+        "170" => "雪",
+        "181" => "雷",
+        _ => "不明",
+    }
+}
+
+// APIキーがない場合のデモ用の天気データを返す関数
+//
+// `location`は表示用に名前をそのまま埋め込むだけで、実際のデータは
+// 固定のモックから時刻に応じて選ぶ。`options.units`が"imperial"の場合は
+// 気温を華氏に変換する
+pub fn get_weather_demo(location: &str, options: &WeatherOptions) -> WeatherInfo {
+    let mock_weathers = [
+        WeatherInfo {
+            description: format!("{} - 晴れ", location),
+            temperature: 22.5,
+            weather_code: "100".to_string(),
+            humidity: 45,
+            icon: "01d".to_string(),
+            forecast: Vec::new(),
+            trend: "→".to_string(),
+        },
+        WeatherInfo {
+            description: format!("{} - 曇り", location),
+            temperature: 18.3,
+            weather_code: "110".to_string(),
+            humidity: 65,
+            icon: "03d".to_string(),
+            forecast: Vec::new(),
+            trend: "→".to_string(),
+        },
+        WeatherInfo {
+            description: format!("{} - 小雨", location),
+            temperature: 15.8,
+            weather_code: "120".to_string(),
+            humidity: 78,
+            icon: "10d".to_string(),
+            forecast: Vec::new(),
+            trend: "→".to_string(),
+        },
+    ];
+
+    // 現在の時刻から適当なデータを選択
+    use chrono::Timelike;
+    let now = chrono::Local::now();
+    let index = (now.minute() % 3) as usize;
+    let mut weather = mock_weathers[index].clone();
+
+    if options.units == "imperial" {
+        weather.temperature = celsius_to_fahrenheit(weather.temperature);
+    }
+
+    weather
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icon_code_for_does_not_double_assign_rain_or_thunder() {
+        // 160（雨）は13のみ、181（雷）は11のみに属するべき
+        assert_eq!(icon_code_for("160"), "13");
+        assert_eq!(icon_code_for("181"), "11");
+    }
+
+    #[test]
+    fn icon_code_for_maps_known_codes() {
+        assert_eq!(icon_code_for("100"), "01");
+        assert_eq!(icon_code_for("110"), "09");
+        assert_eq!(icon_code_for("999"), "50");
+    }
+
+    #[test]
+    fn resolve_icon_picks_day_suffix_within_default_daylight_hours() {
+        assert_eq!(resolve_icon("100", 12, None), "01d");
+        assert_eq!(resolve_icon("100", 2, None), "01n");
+    }
+
+    #[test]
+    fn resolve_icon_uses_provided_sunrise_and_sunset() {
+        assert_eq!(resolve_icon("100", 5, Some((4, 19))), "01d");
+        assert_eq!(resolve_icon("100", 20, Some((4, 19))), "01n");
+    }
+
+    fn json_times(times: &[&str]) -> Vec<serde_json::Value> {
+        times.iter().map(|t| serde_json::Value::from(*t)).collect()
+    }
+
+    #[test]
+    fn nearest_temp_index_finds_exact_match() {
+        let temp_times = json_times(&["2024-01-01T00:00:00+09:00", "2024-01-01T09:00:00+09:00"]);
+        assert_eq!(nearest_temp_index("2024-01-01T09:00:00+09:00", &temp_times), Some(1));
+    }
+
+    #[test]
+    fn nearest_temp_index_picks_closest_when_granularity_differs() {
+        // 天気は18時台、気温系列は0時/9時の2コマしか無いケース
+        let temp_times = json_times(&["2024-01-01T00:00:00+09:00", "2024-01-01T09:00:00+09:00"]);
+        assert_eq!(nearest_temp_index("2024-01-01T18:00:00+09:00", &temp_times), Some(1));
+    }
+
+    #[test]
+    fn nearest_temp_index_returns_none_for_unparseable_times() {
+        let temp_times = json_times(&["not-a-timestamp"]);
+        assert_eq!(nearest_temp_index("2024-01-01T09:00:00+09:00", &temp_times), None);
+    }
+
+    fn forecast_entry(temperature: f64) -> ForecastEntry {
+        ForecastEntry {
+            time: String::new(),
+            temperature,
+            weather_code: String::new(),
+            icon: String::new(),
+        }
+    }
+
+    #[test]
+    fn compute_trend_rises_when_next_entry_is_warmer() {
+        assert_eq!(compute_trend(20.0, &[forecast_entry(22.0)]), "↑");
+    }
+
+    #[test]
+    fn compute_trend_falls_when_next_entry_is_cooler() {
+        assert_eq!(compute_trend(20.0, &[forecast_entry(18.0)]), "↓");
+    }
+
+    #[test]
+    fn compute_trend_is_flat_within_one_degree_or_with_no_forecast() {
+        assert_eq!(compute_trend(20.0, &[forecast_entry(20.5)]), "→");
+        assert_eq!(compute_trend(20.0, &[]), "→");
+    }
+
+    #[test]
+    fn celsius_to_fahrenheit_converts_known_points() {
+        assert_eq!(celsius_to_fahrenheit(0.0), 32.0);
+        assert_eq!(celsius_to_fahrenheit(100.0), 212.0);
+    }
+}